@@ -0,0 +1,88 @@
+//! A forward may-alias analysis computing, for each program point, the set
+//! of locals that may have had their address taken and so could be written
+//! to indirectly through a reference or raw pointer elsewhere in the body.
+//!
+//! Modeled on rustc's own `MaybeBorrowedLocals`: taking `&x`, `&mut x`, or
+//! `&raw x` gens the base local of the borrowed place, and - because we
+//! don't track when a borrow goes out of scope - that local is never
+//! killed for the rest of the body. [`TaintAnalysis`] consults the result
+//! to conservatively taint every possibly-aliased local on an assignment
+//! through a dereferenced place.
+//!
+//! [`TaintAnalysis`]: super::taint_analysis::TaintAnalysis
+
+use rustc_index::bit_set::BitSet;
+use rustc_middle::mir::visit::Visitor;
+use rustc_middle::mir::{
+    BasicBlock, Body, HasLocalDecls, Local, Location, Operand, Place, Rvalue, Statement,
+    Terminator,
+};
+
+use rustc_mir::dataflow::{Analysis, AnalysisDomain, Forward};
+
+/// Locals that may have been aliased by a reference, raw pointer, or
+/// `&raw` borrow taken somewhere in the body.
+pub struct MaybeBorrowedLocals;
+
+impl<'tcx> AnalysisDomain<'tcx> for MaybeBorrowedLocals {
+    type Domain = BitSet<Local>;
+    const NAME: &'static str = "MaybeBorrowedLocals";
+
+    type Direction = Forward;
+
+    fn bottom_value(&self, body: &Body<'tcx>) -> Self::Domain {
+        // bottom = never borrowed
+        BitSet::new_empty(body.local_decls().len())
+    }
+
+    fn initialize_start_block(&self, _body: &Body<'tcx>, _state: &mut Self::Domain) {
+        // No locals are borrowed before the body starts executing.
+    }
+}
+
+impl<'tcx> Analysis<'tcx> for MaybeBorrowedLocals {
+    fn apply_statement_effect(
+        &self,
+        state: &mut Self::Domain,
+        statement: &Statement<'tcx>,
+        location: Location,
+    ) {
+        BorrowedLocalsVisitor { state }.visit_statement(statement, location);
+    }
+
+    fn apply_terminator_effect(
+        &self,
+        state: &mut Self::Domain,
+        terminator: &Terminator<'tcx>,
+        location: Location,
+    ) {
+        BorrowedLocalsVisitor { state }.visit_terminator(terminator, location);
+    }
+
+    fn apply_call_return_effect(
+        &self,
+        _state: &mut Self::Domain,
+        _block: BasicBlock,
+        _func: &Operand<'tcx>,
+        _args: &[Operand<'tcx>],
+        _return_place: Place<'tcx>,
+    ) {
+        // A call's return place is never itself a borrow.
+    }
+}
+
+struct BorrowedLocalsVisitor<'a> {
+    state: &'a mut BitSet<Local>,
+}
+
+impl<'tcx> Visitor<'tcx> for BorrowedLocalsVisitor<'_> {
+    fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+        if let Rvalue::Ref(_, _, place) | Rvalue::AddressOf(_, place) = rvalue {
+            // Gen-only: we never have enough information to prove a borrow
+            // has ended, so once a local's address is taken it stays
+            // possibly-aliased for the remainder of the body.
+            self.state.insert(place.local);
+        }
+        self.super_rvalue(rvalue, location);
+    }
+}
@@ -0,0 +1,19 @@
+//! Diagnostics emitted by [`TaintAnalysis`].
+//!
+//! [`TaintAnalysis`]: super::taint_analysis::TaintAnalysis
+
+use rustc_macros::SessionDiagnostic;
+use rustc_span::Span;
+
+/// A tainted value reached a sink declared to reject its label.
+#[derive(SessionDiagnostic)]
+#[error(taint::tainted_sink, code = "T0001")]
+pub struct TaintedSink {
+    pub fn_name: String,
+    /// The comma-separated labels the sink rejects that the argument
+    /// actually carried, e.g. `"sql"` or `"sql, shell"`.
+    pub labels: String,
+    #[primary_span]
+    #[label]
+    pub span: Span,
+}
@@ -0,0 +1,118 @@
+//! An opt-in Graphviz dump of [`TaintAnalysis`]'s per-point dataflow state,
+//! for inspecting why a local ended up (or didn't end up) tainted. Mirrors
+//! rustc's own `framework::graphviz` dataflow diagrams: one node per basic
+//! block, annotated with the tainted move-paths on entry and after every
+//! statement/terminator.
+//!
+//! [`TaintAnalysis`]: super::taint_analysis::TaintAnalysis
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+
+use rustc_middle::mir::{Body, Location};
+
+use rustc_mir::dataflow::{Results, ResultsCursor};
+
+use super::move_paths::MoveData;
+use super::taint_analysis::TaintAnalysis;
+use super::taint_domain::TaintState;
+
+/// Set this environment variable to opt into dumping a `.dot` file per
+/// analyzed body. Left unset, [`dump_dataflow`] is a no-op.
+pub const DUMP_ENV_VAR: &str = "TAINT_DUMP_DATAFLOW";
+
+/// Render `results` as a Graphviz DOT file in the current directory,
+/// named after the body's `DefId`, if [`DUMP_ENV_VAR`] is set.
+pub fn dump_dataflow<'tcx>(
+    body: &Body<'tcx>,
+    results: &Results<'tcx, TaintAnalysis<'tcx, '_>>,
+) -> io::Result<()> {
+    if env::var_os(DUMP_ENV_VAR).is_none() {
+        return Ok(());
+    }
+
+    let path = format!("{:?}.taint.dot", body.source.def_id()).replace('/', "_");
+    let mut file = File::create(path)?;
+    write_dot(&mut file, body, results)
+}
+
+fn write_dot<'tcx>(
+    out: &mut impl Write,
+    body: &Body<'tcx>,
+    results: &Results<'tcx, TaintAnalysis<'tcx, '_>>,
+) -> io::Result<()> {
+    let move_data = &results.analysis.move_data;
+    let mut cursor = ResultsCursor::new(body, results);
+
+    writeln!(out, "digraph TaintAnalysis {{")?;
+    writeln!(out, "    node [shape=box fontname=monospace]")?;
+
+    for (block, data) in body.basic_blocks().iter_enumerated() {
+        cursor.seek_to_block_start(block);
+        let mut label = format!(
+            "bb{}:\\lon entry: {}\\l",
+            block.index(),
+            format_state(move_data, cursor.get())
+        );
+
+        for (statement_index, statement) in data.statements.iter().enumerate() {
+            cursor.seek_after_primary_effect(Location {
+                block,
+                statement_index,
+            });
+            label.push_str(&format!(
+                "{}\\l  -&gt; {}\\l",
+                escape_dot_label(&format!("{:?}", statement)),
+                format_state(move_data, cursor.get())
+            ));
+        }
+
+        if let Some(terminator) = &data.terminator {
+            cursor.seek_after_primary_effect(Location {
+                block,
+                statement_index: data.statements.len(),
+            });
+            label.push_str(&format!(
+                "{}\\l  -&gt; {}\\l",
+                escape_dot_label(&format!("{:?}", terminator.kind)),
+                format_state(move_data, cursor.get())
+            ));
+
+            writeln!(out, "    bb{} [label=\"{}\"]", block.index(), label)?;
+            for successor in terminator.successors() {
+                writeln!(out, "    bb{} -> bb{}", block.index(), successor.index())?;
+            }
+        } else {
+            writeln!(out, "    bb{} [label=\"{}\"]", block.index(), label)?;
+        }
+    }
+
+    writeln!(out, "}}")
+}
+
+/// Escape `"` and `\` so `text` can be spliced into a double-quoted DOT
+/// `label="..."` string. MIR's `Debug` output routinely contains both (e.g.
+/// a statement assigning a string literal, `_1 = const "foo"`), and an
+/// unescaped quote there would terminate the label early and produce a
+/// `.dot` file Graphviz can't parse.
+fn escape_dot_label(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Render the tainted move-paths in `state` as `{_1{sql}, _2.0{env}}`, or
+/// `{}` if nothing is tainted.
+fn format_state(move_data: &MoveData, state: &TaintState) -> String {
+    let parts: Vec<_> = state
+        .iter_tainted()
+        .map(|(path, labels)| {
+            let labels = labels
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("{}{{{}}}", move_data.describe_path(path), labels)
+        })
+        .collect();
+    format!("{{{}}}", parts.join(", "))
+}
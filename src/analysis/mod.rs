@@ -0,0 +1,6 @@
+pub mod borrowed_locals;
+pub mod errors;
+pub mod graphviz;
+pub mod move_paths;
+pub mod taint_analysis;
+pub mod taint_domain;
@@ -0,0 +1,206 @@
+//! A coarse move-path abstraction giving the taint analysis field- and
+//! index-sensitivity, modeled on rustc's own `rustc_mir::dataflow::move_paths`.
+//!
+//! Each [`MovePathIndex`] identifies a `(Local, projection-prefix)` pair.
+//! The projection is abstracted by [`abs_domain`]: `Field` and constant
+//! `ConstantIndex` elements are kept, while a dynamic `Index` or a `Deref`
+//! can't be resolved statically and so truncate the path to its nearest
+//! known-precise ancestor - exactly as rustc's own move-path builder does.
+//! A path implicitly covers every path that has it as a prefix, so tainting
+//! `s` also taints `s.a`, and checking `s.a` for taint also finds a taint
+//! recorded on `s`.
+
+use std::collections::HashMap;
+
+use rustc_index::newtype_index;
+use rustc_index::vec::IndexVec;
+use rustc_middle::mir::visit::Visitor;
+use rustc_middle::mir::{Body, Local, Place, PlaceElem, ProjectionElem};
+
+newtype_index! {
+    /// A `Local` together with a chain of field/constant-index
+    /// projections from it.
+    pub struct MovePathIndex {
+        DEBUG_FORMAT = "mp{}"
+    }
+}
+
+/// The abstracted form of a single projection element, used to key a move
+/// path. Only `Field` and `ConstantIndex` are precise enough to keep;
+/// everything else abstracts away to the parent path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AbsElem {
+    Field(u32),
+    ConstantIndex(u64),
+}
+
+/// Keep `Field` and constant-`Index` elements, truncating the projection
+/// at the first element we can't resolve statically (a dynamic `Index` or
+/// a `Deref`), since nothing past that point can be attributed to a
+/// specific path without aliasing information.
+fn abs_domain(projection: &[PlaceElem<'_>]) -> Vec<AbsElem> {
+    let mut abs = Vec::new();
+    for elem in projection {
+        match *elem {
+            ProjectionElem::Field(field, _) => abs.push(AbsElem::Field(field.index() as u32)),
+            ProjectionElem::ConstantIndex { offset, .. } => {
+                abs.push(AbsElem::ConstantIndex(offset))
+            }
+            // A variant doesn't narrow the path further; skip it without
+            // truncating.
+            ProjectionElem::Downcast(..) => {}
+            ProjectionElem::Deref | ProjectionElem::Index(_) | ProjectionElem::Subslice { .. } => {
+                break
+            }
+        }
+    }
+    abs
+}
+
+struct MovePath {
+    local: Local,
+    abs: Vec<AbsElem>,
+    parent: Option<MovePathIndex>,
+}
+
+/// The move-path table for a single body: every `(Local, projection-prefix)`
+/// combination touched by the body, keyed for O(1) lookup.
+pub struct MoveData {
+    paths: IndexVec<MovePathIndex, MovePath>,
+    lookup: HashMap<(Local, Vec<AbsElem>), MovePathIndex>,
+}
+
+impl MoveData {
+    /// Walk `body` and register a move path for every place it mentions,
+    /// plus a base path for every local (even ones never projected into).
+    pub fn gather(body: &Body<'_>) -> Self {
+        let mut builder = MoveDataBuilder {
+            paths: IndexVec::new(),
+            lookup: HashMap::new(),
+        };
+        for local in body.local_decls.indices() {
+            builder.path_for_key(local, Vec::new());
+        }
+        builder.visit_body(body);
+        MoveData {
+            paths: builder.paths,
+            lookup: builder.lookup,
+        }
+    }
+
+    /// The number of distinct move paths tracked for this body, used to
+    /// size the dataflow domain.
+    pub fn num_paths(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// The move path for `place`, falling back to the nearest registered
+    /// ancestor if `place`'s exact projection was never seen directly
+    /// (every local always has at least its base path registered).
+    pub fn path_for(&self, place: &Place<'_>) -> MovePathIndex {
+        let mut abs = abs_domain(place.projection);
+        loop {
+            if let Some(&idx) = self.lookup.get(&(place.local, abs.clone())) {
+                return idx;
+            }
+            abs.pop().expect("every local has a registered base path");
+        }
+    }
+
+    /// `path` itself, then its parent, grandparent, and so on up to the
+    /// path's local.
+    pub fn ancestors(&self, path: MovePathIndex) -> impl Iterator<Item = MovePathIndex> + '_ {
+        std::iter::successors(Some(path), move |&p| self.paths[p].parent)
+    }
+
+    /// Every path that has `path` as a prefix, including `path` itself.
+    pub fn descendants(&self, path: MovePathIndex) -> impl Iterator<Item = MovePathIndex> + '_ {
+        self.paths
+            .indices()
+            .filter(move |&candidate| self.ancestors(candidate).any(|p| p == path))
+    }
+
+    /// The move path for `path.field`, if that exact field projection was
+    /// registered during `gather` (i.e. some place in the body projects
+    /// into it).
+    /// Returns `None` if nothing ever projects into that field directly,
+    /// in which case callers should fall back to tainting `path` itself.
+    ///
+    /// For struct/tuple/closure/generator fields only - a fixed-size array's
+    /// elements are projected via `ConstantIndex` instead, so use
+    /// [`array_element`](Self::array_element) for those.
+    pub fn field(&self, path: MovePathIndex, field: u32) -> Option<MovePathIndex> {
+        self.child(path, AbsElem::Field(field))
+    }
+
+    /// The move path for `path[index]`, if that exact constant-index
+    /// projection was registered during `gather`. Returns `None` if nothing
+    /// ever projects into that index directly, in which case callers should
+    /// fall back to tainting `path` itself.
+    pub fn array_element(&self, path: MovePathIndex, index: u64) -> Option<MovePathIndex> {
+        self.child(path, AbsElem::ConstantIndex(index))
+    }
+
+    /// The move path for `path` extended by a single abstracted projection
+    /// element, if that exact extension was registered during `gather`.
+    fn child(&self, path: MovePathIndex, elem: AbsElem) -> Option<MovePathIndex> {
+        let mut abs = self.paths[path].abs.clone();
+        abs.push(elem);
+        self.lookup.get(&(self.paths[path].local, abs)).copied()
+    }
+
+    /// A human-readable name for `path`, e.g. `_3.0` for the first field of
+    /// local `_3`. Used for diagnostics and the dataflow graphviz dump.
+    pub fn describe_path(&self, path: MovePathIndex) -> String {
+        let MovePath { local, abs, .. } = &self.paths[path];
+        let mut out = format!("_{}", local.index());
+        for elem in abs {
+            match elem {
+                AbsElem::Field(field) => out.push_str(&format!(".{}", field)),
+                AbsElem::ConstantIndex(index) => out.push_str(&format!("[{}]", index)),
+            }
+        }
+        out
+    }
+}
+
+struct MoveDataBuilder {
+    paths: IndexVec<MovePathIndex, MovePath>,
+    lookup: HashMap<(Local, Vec<AbsElem>), MovePathIndex>,
+}
+
+impl MoveDataBuilder {
+    fn path_for_key(&mut self, local: Local, abs: Vec<AbsElem>) -> MovePathIndex {
+        if let Some(&idx) = self.lookup.get(&(local, abs.clone())) {
+            return idx;
+        }
+
+        let parent = match abs.split_last() {
+            None => None,
+            Some((_, prefix)) => Some(self.path_for_key(local, prefix.to_vec())),
+        };
+
+        let idx = self.paths.push(MovePath {
+            local,
+            abs: abs.clone(),
+            parent,
+        });
+        self.lookup.insert((local, abs), idx);
+        idx
+    }
+
+    fn register(&mut self, place: &Place<'_>) -> MovePathIndex {
+        self.path_for_key(place.local, abs_domain(place.projection))
+    }
+}
+
+impl<'tcx> Visitor<'tcx> for MoveDataBuilder {
+    fn visit_place(
+        &mut self,
+        place: &Place<'tcx>,
+        _context: rustc_middle::mir::visit::PlaceContext,
+        _location: rustc_middle::mir::Location,
+    ) {
+        self.register(place);
+    }
+}
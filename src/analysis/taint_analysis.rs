@@ -1,50 +1,195 @@
+use std::collections::BTreeSet;
+
 use rustc_index::bit_set::BitSet;
+use rustc_index::vec::IndexVec;
 use rustc_middle::mir::{
-    visit::Visitor, BasicBlock, Body, HasLocalDecls, Local, Location, Operand, Place, Rvalue,
+    visit::Visitor, AggregateKind, BasicBlock, Body, Local, Location, Operand, Place, Rvalue,
     Statement, StatementKind, Terminator, TerminatorKind,
 };
+use rustc_middle::ty::TyCtxt;
 
-use rustc_mir::dataflow::{Analysis, AnalysisDomain, Forward};
+use rustc_mir::dataflow::{Analysis, AnalysisDomain, Forward, Results, ResultsCursor};
 use rustc_session::Session;
 use rustc_span::Span;
 
 use tracing::instrument;
 
-use crate::eval::AttrInfo;
+use crate::eval::{AttrInfo, Classification, TaintLabel};
 
-use super::taint_domain::TaintDomain;
+use super::borrowed_locals::MaybeBorrowedLocals;
+use super::move_paths::{MoveData, MovePathIndex};
+use super::taint_domain::{TaintDomain, TaintState};
 
-/// A dataflow analysis that tracks whether a value may carry a taint.
+/// A dataflow analysis that tracks which taint labels a value may carry.
 ///
-/// Taints are introduced through sources, and consumed by sinks.
-/// Ideally, a sink never consumes a tainted value - this should result in an error.
+/// Labels are introduced through sources and consumed by sinks; a sink
+/// declared to reject a label must never observe it. Labels let the
+/// analysis tell apart, say, `"network"` taint from `"env"` taint, so a
+/// sink can reject one while accepting the other even though both flow
+/// through the same code.
 pub struct TaintAnalysis<'tcx, 'v> {
     session: &'tcx Session,
     info: &'v AttrInfo,
+    body: &'tcx Body<'tcx>,
+    /// The move-path table for `body`, giving the analysis field- and
+    /// index-sensitivity instead of tracking taint per whole local.
+    ///
+    /// `pub(crate)` so [`graphviz`](super::graphviz) can render move-path
+    /// names for a finished [`Results`] without the dump needing its own
+    /// copy of the table.
+    pub(crate) move_data: MoveData,
+    /// Locals that may be aliased by a reference or raw pointer
+    /// immediately before each statement/terminator in `body`, used to
+    /// conservatively propagate taint through assignments made via a
+    /// dereferenced place. Indexed by `[location.block][location.statement_index]`.
+    ///
+    /// Precomputed once with a single forward walk over `borrowed_locals`'
+    /// results, rather than seeking a fresh `ResultsCursor` from each
+    /// block's start on every `apply_statement_effect`/
+    /// `apply_terminator_effect` call - that would be quadratic in block
+    /// length, and re-run on every fixpoint iteration besides.
+    aliased_by_location: IndexVec<BasicBlock, Vec<BitSet<Local>>>,
 }
 
 impl<'tcx, 'v> TaintAnalysis<'tcx, 'v> {
-    pub fn new(session: &'tcx Session, info: &'v AttrInfo) -> Self {
-        TaintAnalysis { session, info }
+    pub fn new(
+        tcx: TyCtxt<'tcx>,
+        session: &'tcx Session,
+        info: &'v AttrInfo,
+        body: &'tcx Body<'tcx>,
+    ) -> Self {
+        let move_data = MoveData::gather(body);
+        let borrowed_locals = MaybeBorrowedLocals.into_engine(tcx, body).iterate_to_fixpoint();
+        let aliased_by_location = Self::precompute_aliased_locals(body, &borrowed_locals);
+
+        TaintAnalysis {
+            session,
+            info,
+            body,
+            move_data,
+            aliased_by_location,
+        }
+    }
+
+    /// Walk `borrowed_locals` forward once and record the aliased-locals
+    /// state before every statement/terminator in `body`.
+    fn precompute_aliased_locals(
+        body: &'tcx Body<'tcx>,
+        borrowed_locals: &Results<'tcx, MaybeBorrowedLocals>,
+    ) -> IndexVec<BasicBlock, Vec<BitSet<Local>>> {
+        let mut cursor = ResultsCursor::new(body, borrowed_locals);
+        let mut aliased_by_location = IndexVec::new();
+
+        for (block, data) in body.basic_blocks().iter_enumerated() {
+            cursor.seek_to_block_start(block);
+
+            let mut aliased_by_statement = Vec::with_capacity(data.statements.len() + 1);
+            for statement_index in 0..=data.statements.len() {
+                aliased_by_statement.push(cursor.get().clone());
+                if statement_index < data.statements.len() {
+                    cursor.seek_after_primary_effect(Location {
+                        block,
+                        statement_index,
+                    });
+                }
+            }
+            aliased_by_location.push(aliased_by_statement);
+        }
+
+        aliased_by_location
+    }
+
+    /// Run the analysis on `body` to a fixpoint, then dump a Graphviz
+    /// visualization of the result if `TAINT_DUMP_DATAFLOW` is set
+    /// (see [`graphviz::dump_dataflow`](super::graphviz::dump_dataflow)).
+    pub fn run(
+        tcx: TyCtxt<'tcx>,
+        session: &'tcx Session,
+        info: &'v AttrInfo,
+        body: &'tcx Body<'tcx>,
+    ) -> Results<'tcx, Self> {
+        let results = Self::new(tcx, session, info, body)
+            .into_engine(tcx, body)
+            .iterate_to_fixpoint();
+
+        if let Err(err) = super::graphviz::dump_dataflow(body, &results) {
+            session.err(&format!("failed to write taint dataflow graph: {}", err));
+        }
+
+        results
+    }
+
+    /// The locals that may be aliased at `location`, per the
+    /// `MaybeBorrowedLocals` pass.
+    fn aliased_locals_at(&self, location: Location) -> &BitSet<Local> {
+        &self.aliased_by_location[location.block][location.statement_index]
+    }
+
+    /// Check a sink call's arguments against the real, precise incoming
+    /// state and report which of the sink's rejected labels an argument
+    /// actually carried.
+    fn check_sink(
+        &self,
+        state: &TaintState,
+        name: String,
+        rejects: &BTreeSet<TaintLabel>,
+        args: &[Operand],
+        span: Span,
+    ) {
+        for arg in args {
+            let place = match arg.place() {
+                Some(place) => place,
+                None => continue,
+            };
+
+            let path = self.move_data.path_for(&place);
+            let violated: BTreeSet<_> = state
+                .labels_at(&self.move_data, path)
+                .intersection(rejects)
+                .cloned()
+                .collect();
+
+            if !violated.is_empty() {
+                let labels = violated
+                    .iter()
+                    .map(TaintLabel::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                self.session.emit_err(super::errors::TaintedSink {
+                    fn_name: name,
+                    labels,
+                    span,
+                });
+                return;
+            }
+        }
     }
 }
 
 impl<'tcx, 'v> AnalysisDomain<'tcx> for TaintAnalysis<'tcx, 'v> {
-    type Domain = BitSet<Local>;
+    type Domain = TaintState;
     const NAME: &'static str = "TaintAnalysis";
 
     type Direction = Forward;
 
-    fn bottom_value(&self, body: &Body<'tcx>) -> Self::Domain {
-        // bottom = untainted
-        BitSet::new_empty(body.local_decls().len())
+    fn bottom_value(&self, _body: &Body<'tcx>) -> Self::Domain {
+        // bottom = no label reaches any path
+        TaintState::new_empty(self.move_data.num_paths())
     }
 
     fn initialize_start_block(&self, _body: &Body<'tcx>, _state: &mut Self::Domain) {
-        // Locals start out being untainted
+        // Locals start out carrying no taint.
     }
 }
 
+// Every effect here reads and writes the real incoming `TaintState`
+// directly. Which label an assignment's destination ends up with depends
+// on which label its source currently carries, so nothing in this
+// analysis is state-independent enough to precompute as a cacheable
+// gen/kill pair - not even a call's own source/sanitizer effect on its
+// return place, since whether that effect actually changes anything still
+// has to be checked against the state flowing into it.
 impl<'tcx, 'v> Analysis<'tcx> for TaintAnalysis<'tcx, 'v> {
     fn apply_statement_effect(
         &self,
@@ -52,8 +197,12 @@ impl<'tcx, 'v> Analysis<'tcx> for TaintAnalysis<'tcx, 'v> {
         statement: &Statement<'tcx>,
         location: Location,
     ) {
-        self.transfer_function(state, self.info)
-            .visit_statement(statement, location);
+        TransferFunction {
+            move_data: &self.move_data,
+            aliased: self.aliased_locals_at(location),
+            state,
+        }
+        .visit_statement(statement, location);
     }
 
     fn apply_terminator_effect(
@@ -62,49 +211,76 @@ impl<'tcx, 'v> Analysis<'tcx> for TaintAnalysis<'tcx, 'v> {
         terminator: &Terminator<'tcx>,
         location: Location,
     ) {
-        self.transfer_function(state, self.info)
-            .visit_terminator(terminator, location);
+        if let TerminatorKind::Call {
+            func, args, fn_span, ..
+        } = &terminator.kind
+        {
+            let name = func
+                .constant()
+                .expect("Operand is not a function")
+                .to_string();
+
+            if let Some(Classification::Sink { rejects }) = self.info.classify(&name) {
+                self.check_sink(state, name, rejects, args, *fn_span);
+            }
+        }
+
+        TransferFunction {
+            move_data: &self.move_data,
+            aliased: self.aliased_locals_at(location),
+            state,
+        }
+        .visit_terminator(terminator, location);
     }
 
     fn apply_call_return_effect(
         &self,
-        _state: &mut Self::Domain,
+        state: &mut Self::Domain,
         _block: BasicBlock,
-        _func: &Operand<'tcx>,
-        _args: &[Operand<'tcx>],
-        _return_place: Place<'tcx>,
+        func: &Operand<'tcx>,
+        args: &[Operand<'tcx>],
+        return_place: Place<'tcx>,
     ) {
-        // do nothing
-    }
-}
-
-struct TransferFunction<'tcx, T> {
-    state: &'tcx mut T,
-    session: &'tcx Session,
-    info: &'tcx AttrInfo,
-}
+        // The destination of a `Call` terminator is only live on the
+        // return edge, so the source/sanitizer effect has to be applied
+        // here rather than mid-terminator - that would have the call's
+        // own effect on `return_place` get overwritten by the
+        // terminator's own transfer function.
+        let name = func
+            .constant()
+            .expect("Operand is not a function")
+            .to_string();
 
-impl<'tcx, 'v> TaintAnalysis<'tcx, 'v> {
-    fn transfer_function<T>(
-        &'tcx self,
-        state: &'tcx mut T,
-        info: &'v AttrInfo,
-    ) -> TransferFunction<'tcx, T> {
-        TransferFunction {
-            state,
-            session: self.session,
-            info: self.info,
+        let path = self.move_data.path_for(&return_place);
+        match self.info.classify(&name) {
+            Some(Classification::Source { label }) => {
+                state.mark_tainted(&self.move_data, path, std::iter::once(label.clone()).collect());
+            }
+            Some(Classification::Sanitizer { clears }) => {
+                // The result carries every label its arguments carried,
+                // minus the ones this sanitizer clears.
+                let mut labels = BTreeSet::new();
+                for place in args.iter().filter_map(Operand::place) {
+                    labels.extend(state.labels_at(&self.move_data, self.move_data.path_for(&place)));
+                }
+                state.mark_untainted(&self.move_data, path);
+                state.mark_tainted(&self.move_data, path, labels);
+                state.clear_labels(&self.move_data, path, clears);
+            }
+            Some(Classification::Sink { .. }) | None => (),
         }
     }
 }
 
-impl<'tcx, T: std::fmt::Debug> std::fmt::Debug for TransferFunction<'tcx, T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{:?}", &self.state))
-    }
+/// Builds the effect of a single statement or terminator directly against
+/// the live incoming [`TaintState`].
+struct TransferFunction<'a> {
+    move_data: &'a MoveData,
+    aliased: &'a BitSet<Local>,
+    state: &'a mut TaintState,
 }
 
-impl<'tcx, T: TaintDomain<Local> + std::fmt::Debug> Visitor<'tcx> for TransferFunction<'_, T> {
+impl<'tcx> Visitor<'tcx> for TransferFunction<'_> {
     fn visit_statement(&mut self, statement: &Statement<'tcx>, _: Location) {
         let Statement { source_info, kind } = statement;
 
@@ -121,111 +297,136 @@ impl<'tcx, T: TaintDomain<Local> + std::fmt::Debug> Visitor<'tcx> for TransferFu
     }
 
     fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, _: Location) {
-        let Terminator { source_info, kind } = terminator;
+        let Terminator { source_info, .. } = terminator;
 
         self.visit_source_info(source_info);
 
-        match kind {
-            TerminatorKind::Goto { .. } => {}
-            TerminatorKind::SwitchInt { .. } => {}
-            TerminatorKind::Return => {}
-            TerminatorKind::Call {
-                func,
-                args,
-                destination,
-                fn_span,
-                ..
-            } => self.t_visit_call(func, args, destination, fn_span),
-            TerminatorKind::Assert { .. } => {}
-            _ => {}
-        }
+        // Calls only affect the destination on the return edge (see
+        // `TaintAnalysis::apply_call_return_effect`); sink-checking lives
+        // in `TaintAnalysis::apply_terminator_effect` instead.
     }
 }
 
-impl<'tcx, T> TransferFunction<'tcx, T>
-where
-    Self: Visitor<'tcx>,
-    T: TaintDomain<Local> + std::fmt::Debug,
-{
-    #[instrument]
-    fn t_visit_assign(&mut self, place: &Place, rvalue: &Rvalue) {
+impl<'tcx> TransferFunction<'_> {
+    /// The labels `operand` currently carries: those reaching its place,
+    /// or none if it's a constant.
+    fn operand_labels(&self, operand: &Operand) -> BTreeSet<TaintLabel> {
+        match operand.place() {
+            Some(place) => self
+                .state
+                .labels_at(self.move_data, self.move_data.path_for(&place)),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// The labels `rvalue` as a whole currently carries, as the union of
+    /// every operand or place it reads - ignoring any per-field precision
+    /// a caller might apply on top (see `t_visit_assign`'s `Aggregate`
+    /// arm). Shared by the direct- and indirect-assignment paths so the
+    /// set of `Rvalue` kinds they understand can't drift apart again.
+    fn rvalue_labels(&self, rvalue: &Rvalue) -> BTreeSet<TaintLabel> {
         match rvalue {
-            // If we assign a constant to a place, the place is clean.
-            Rvalue::Use(Operand::Constant(_)) | Rvalue::UnaryOp(_, Operand::Constant(_)) => {
-                self.state.mark_untainted(place.local)
+            Rvalue::Use(op)
+            | Rvalue::UnaryOp(_, op)
+            | Rvalue::Repeat(op, _)
+            | Rvalue::Cast(_, op, _) => self.operand_labels(op),
+
+            Rvalue::BinaryOp(_, box (a, b)) | Rvalue::CheckedBinaryOp(_, box (a, b)) => {
+                let mut labels = self.operand_labels(a);
+                labels.extend(self.operand_labels(b));
+                labels
             }
 
-            // Otherwise we propagate the taint
-            Rvalue::Use(Operand::Copy(f) | Operand::Move(f)) => {
-                self.state.propagate(f.local, place.local);
+            // A reference, raw pointer, slice length, or discriminant all
+            // inherit the taint of the place they read.
+            Rvalue::Ref(_, _, place)
+            | Rvalue::AddressOf(_, place)
+            | Rvalue::Len(place)
+            | Rvalue::Discriminant(place) => {
+                self.state.labels_at(self.move_data, self.move_data.path_for(place))
             }
 
-            Rvalue::BinaryOp(_, box b) | Rvalue::CheckedBinaryOp(_, box b) => match b {
-                (Operand::Constant(_), Operand::Constant(_)) => {
-                    self.state.mark_untainted(place.local);
-                }
-                (Operand::Copy(a) | Operand::Move(a), Operand::Copy(b) | Operand::Move(b)) => {
-                    if self.state.is_tainted(a.local) || self.state.is_tainted(b.local) {
-                        self.state.mark_tainted(place.local);
-                    } else {
-                        self.state.mark_untainted(place.local);
-                    }
+            Rvalue::Aggregate(_, operands) => {
+                let mut labels = BTreeSet::new();
+                for operand in operands.iter() {
+                    labels.extend(self.operand_labels(operand));
                 }
-                (Operand::Copy(p) | Operand::Move(p), Operand::Constant(_))
-                | (Operand::Constant(_), Operand::Copy(p) | Operand::Move(p)) => {
-                    self.state.propagate(p.local, place.local);
-                }
-            },
-            Rvalue::UnaryOp(_, Operand::Move(p) | Operand::Copy(p)) => {
-                self.state.propagate(p.local, place.local);
+                labels
             }
 
-            Rvalue::Repeat(_, _) => {}
-            Rvalue::Ref(_, _, _) => {}
-            Rvalue::ThreadLocalRef(_) => {}
-            Rvalue::AddressOf(_, _) => {}
-            Rvalue::Len(_) => {}
-            Rvalue::Cast(_, _, _) => {}
-            Rvalue::NullaryOp(_, _) => {}
-            Rvalue::Discriminant(_) => {}
-            Rvalue::Aggregate(_, _) => {}
+            Rvalue::ThreadLocalRef(_) | Rvalue::NullaryOp(_, _) => BTreeSet::new(),
+
+            _ => BTreeSet::new(),
         }
     }
 
     #[instrument]
-    fn t_visit_call(
-        &mut self,
-        func: &Operand,
-        args: &[Operand],
-        destination: &Option<(Place, BasicBlock)>,
-        span: &Span,
-    ) {
-        let name = func
-            .constant()
-            .expect("Operand is not a function")
-            .to_string();
+    fn t_visit_assign(&mut self, place: &Place, rvalue: &Rvalue) {
+        // An assignment through a dereferenced place (`*p = ...`) cannot be
+        // attributed to a single local, since we don't know statically what
+        // `p` points to. Conservatively taint every local `p` may alias
+        // instead of `place`'s own path (which names `p` itself, not its
+        // pointee).
+        if place.is_indirect() {
+            self.t_visit_indirect_assign(rvalue);
+            return;
+        }
 
-        // TODO(Hilmar): Check if function is source, sink or sanitizer.
-    }
+        let dest = self.move_data.path_for(place);
 
-    fn t_visit_source_destination(&mut self, destination: &Option<(Place, BasicBlock)>) {
-        if let Some((place, _)) = destination {
-            self.state.mark_tainted(place.local);
+        match rvalue {
+            // Building a struct/tuple/array taints the whole destination,
+            // but with move paths we can do better: gen only the specific
+            // element each possibly-tainted operand was constructed into,
+            // falling back to the whole destination if that element is
+            // never itself projected into elsewhere in the body. A fixed-
+            // size array's elements are keyed by `ConstantIndex` rather
+            // than `Field` (see `MoveData::array_element`), so which
+            // lookup to use depends on the aggregate kind.
+            Rvalue::Aggregate(kind, operands) => {
+                self.state.mark_untainted(self.move_data, dest);
+                for (index, operand) in operands.iter().enumerate() {
+                    let labels = self.operand_labels(operand);
+                    if !labels.is_empty() {
+                        let element_path = match **kind {
+                            AggregateKind::Array(_) => {
+                                self.move_data.array_element(dest, index as u64)
+                            }
+                            _ => self.move_data.field(dest, index as u32),
+                        }
+                        .unwrap_or(dest);
+                        self.state.mark_tainted(self.move_data, element_path, labels);
+                    }
+                }
+            }
+
+            _ => {
+                let labels = self.rvalue_labels(rvalue);
+                self.state.mark_untainted(self.move_data, dest);
+                if !labels.is_empty() {
+                    self.state.mark_tainted(self.move_data, dest, labels);
+                }
+            }
         }
     }
 
-    fn t_visit_sink(&mut self, name: String, args: &[Operand], span: &Span) {
-        if args.iter().map(|op| op.place()).any(|el| {
-            if let Some(place) = el {
-                self.state.is_tainted(place.local)
-            } else {
-                false
-            }
-        }) {
-            self.session.emit_err(super::errors::TaintedSink {
-                fn_name: name,
-                span: *span,
-            });
+    /// The conservative effect of assigning through a dereferenced place:
+    /// every local that may alias the pointer gets the union of the
+    /// assigned value's current labels, since we cannot prove which one it
+    /// actually points to. This is a deliberate soundness trade-off -
+    /// over-tainting on aliased locals rather than missing a real flow.
+    /// Shares `rvalue_labels` with `t_visit_assign` so this can't silently
+    /// fall behind as new `Rvalue` forms grow propagation support there.
+    fn t_visit_indirect_assign(&mut self, rvalue: &Rvalue) {
+        let labels = self.rvalue_labels(rvalue);
+
+        if labels.is_empty() {
+            return;
+        }
+
+        for local in self.aliased.iter() {
+            let path = self.move_data.path_for(&Place::from(local));
+            self.state.mark_tainted(self.move_data, path, labels.clone());
         }
     }
 }
@@ -0,0 +1,115 @@
+//! The state tracked by [`TaintAnalysis`] at each program point: for every
+//! move-path, the set of [`TaintLabel`]s that may currently reach it,
+//! rather than a single tainted/untainted bit. This is what lets a sink
+//! reject one kind of taint (e.g. `"sql"`) while accepting another passed
+//! through the same code, and lets a sanitizer clear only the labels it's
+//! declared to handle.
+//!
+//! [`TaintAnalysis`]: super::taint_analysis::TaintAnalysis
+
+use std::collections::BTreeSet;
+
+use rustc_index::vec::IndexVec;
+
+use rustc_mir::dataflow::lattice::JoinSemiLattice;
+
+use crate::eval::TaintLabel;
+
+use super::move_paths::{MoveData, MovePathIndex};
+
+/// The taint-label lattice: for each move-path, the set of labels that may
+/// reach it. Bottom (and the starting state) is "no path carries any
+/// label"; join is per-path label-set union.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaintState {
+    labels: IndexVec<MovePathIndex, BTreeSet<TaintLabel>>,
+}
+
+impl TaintState {
+    pub fn new_empty(num_paths: usize) -> Self {
+        TaintState {
+            labels: IndexVec::from_elem_n(BTreeSet::new(), num_paths),
+        }
+    }
+
+    /// Every move-path that currently carries at least one label, paired
+    /// with that label set. Used by the graphviz dump.
+    pub fn iter_tainted(&self) -> impl Iterator<Item = (MovePathIndex, &BTreeSet<TaintLabel>)> {
+        self.labels
+            .iter_enumerated()
+            .filter(|(_, labels)| !labels.is_empty())
+    }
+
+}
+
+impl JoinSemiLattice for TaintState {
+    fn join(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        for (path, labels) in other.labels.iter_enumerated() {
+            for label in labels {
+                changed |= self.labels[path].insert(label.clone());
+            }
+        }
+        changed
+    }
+}
+
+/// A dataflow domain that can track which labels are currently reaching
+/// each move-path.
+pub trait TaintDomain {
+    /// Add `labels` to `path` itself (not its descendants - callers that
+    /// want whole-subtree tainting should also clear or taint the
+    /// descendants they care about).
+    fn mark_tainted(&mut self, move_data: &MoveData, path: MovePathIndex, labels: BTreeSet<TaintLabel>);
+
+    /// Remove every label from `path` and all of its descendants, e.g.
+    /// after assigning a value with no known taint.
+    fn mark_untainted(&mut self, move_data: &MoveData, path: MovePathIndex);
+
+    /// Remove every label in `labels` (and no others) from `path` and all
+    /// of its descendants, e.g. after a sanitizer call clears only the
+    /// labels it's declared to handle.
+    fn clear_labels(&mut self, move_data: &MoveData, path: MovePathIndex, labels: &BTreeSet<TaintLabel>);
+
+    /// Copy the labels reaching `from` onto `to`, replacing whatever `to`
+    /// previously carried.
+    fn propagate(&mut self, move_data: &MoveData, from: MovePathIndex, to: MovePathIndex);
+
+    /// The labels currently reaching `path`: those recorded on `path`
+    /// itself, or on any ancestor (a taint on a struct covers all of its
+    /// fields, since a later read has no way to know it wasn't reached
+    /// through the whole-struct taint).
+    fn labels_at(&self, move_data: &MoveData, path: MovePathIndex) -> BTreeSet<TaintLabel>;
+}
+
+impl TaintDomain for TaintState {
+    fn mark_tainted(&mut self, _move_data: &MoveData, path: MovePathIndex, labels: BTreeSet<TaintLabel>) {
+        self.labels[path].extend(labels);
+    }
+
+    fn mark_untainted(&mut self, move_data: &MoveData, path: MovePathIndex) {
+        for descendant in move_data.descendants(path) {
+            self.labels[descendant].clear();
+        }
+    }
+
+    fn clear_labels(&mut self, move_data: &MoveData, path: MovePathIndex, labels: &BTreeSet<TaintLabel>) {
+        for descendant in move_data.descendants(path) {
+            self.labels[descendant].retain(|label| !labels.contains(label));
+        }
+    }
+
+    fn propagate(&mut self, move_data: &MoveData, from: MovePathIndex, to: MovePathIndex) {
+        let labels = self.labels_at(move_data, from);
+        self.mark_untainted(move_data, to);
+        self.labels[to] = labels;
+    }
+
+    fn labels_at(&self, move_data: &MoveData, path: MovePathIndex) -> BTreeSet<TaintLabel> {
+        let mut labels = BTreeSet::new();
+        for ancestor in move_data.ancestors(path) {
+            labels.extend(self.labels[ancestor].iter().cloned());
+        }
+        labels
+    }
+}
@@ -0,0 +1,58 @@
+//! Classification of callees as taint sources, sinks, or sanitizers.
+//!
+//! A crate under analysis marks functions with `#[taint::source(label =
+//! "...")]`, `#[taint::sink(rejects = "...")]`, and
+//! `#[taint::sanitizer(clears = "...")]` attributes. `AttrInfo` is the
+//! result of collecting those attributes once per crate; [`TaintAnalysis`]
+//! consults it at every call site instead of re-walking the HIR.
+//!
+//! Taint carries a [`TaintLabel`] rather than being a single bit, so the
+//! analysis can tell "network input reached this sink" apart from "env var
+//! reached this sink", and so a sanitizer can be declared to clear only
+//! some of the labels that may be present.
+//!
+//! [`TaintAnalysis`]: crate::analysis::taint_analysis::TaintAnalysis
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// The provenance of a taint, e.g. `"network"` or `"env"`. Distinct sources
+/// are free to share a label (in which case a sink or sanitizer can't tell
+/// them apart either), or each use a label of their own.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaintLabel(pub String);
+
+impl fmt::Display for TaintLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How a callee is classified for the purposes of taint tracking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Classification {
+    /// Introduces taint carrying `label` into its return place.
+    Source { label: TaintLabel },
+    /// Must never be called with an argument carrying a label in `rejects`.
+    Sink { rejects: BTreeSet<TaintLabel> },
+    /// Passes its arguments' labels through to its return place, minus
+    /// every label in `clears`.
+    Sanitizer { clears: BTreeSet<TaintLabel> },
+}
+
+/// Crate-wide table mapping a callee's name to its [`Classification`].
+#[derive(Debug, Default)]
+pub struct AttrInfo {
+    classifications: HashMap<String, Classification>,
+}
+
+impl AttrInfo {
+    pub fn new(classifications: HashMap<String, Classification>) -> Self {
+        AttrInfo { classifications }
+    }
+
+    /// Look up how `name` is classified, if at all.
+    pub fn classify(&self, name: &str) -> Option<&Classification> {
+        self.classifications.get(name)
+    }
+}
@@ -0,0 +1,15 @@
+#![feature(rustc_private)]
+#![feature(box_patterns)]
+
+extern crate rustc_driver;
+extern crate rustc_errors;
+extern crate rustc_hir;
+extern crate rustc_index;
+extern crate rustc_macros;
+extern crate rustc_middle;
+extern crate rustc_mir;
+extern crate rustc_session;
+extern crate rustc_span;
+
+pub mod analysis;
+pub mod eval;
@@ -0,0 +1,306 @@
+//! MIR regression tests for the rvalue forms chunk0-4 added explicit
+//! propagation for: `Aggregate`, `Cast`, `Len`, `Repeat`, and
+//! `Discriminant`, plus struct field precision, per-label sanitizers,
+//! and assignment through a dereferenced place.
+//!
+//! This crate has no attribute-parsing front end of its own (a real driver
+//! builds the [`AttrInfo`] from `#[taint::source]`/`#[taint::sink]`
+//! attributes before calling in), so each test builds one by hand, then
+//! compiles a tiny fixture through `rustc_interface` - the only way to get
+//! a real `TyCtxt` and MIR bodies to run [`TaintAnalysis`] against - and
+//! checks which sink calls it flagged by inspecting the rendered
+//! diagnostics.
+
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+extern crate rustc_interface;
+extern crate rustc_session;
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use rustc_interface::interface::Compiler;
+use rustc_interface::Queries;
+use rustc_session::config::{Input, Options};
+
+use taint::analysis::taint_analysis::TaintAnalysis;
+use taint::eval::{AttrInfo, Classification, TaintLabel};
+
+/// A `Write` sink shared with the compiler session so we can inspect the
+/// diagnostics it rendered after the fact, instead of reaching into the
+/// analysis's internals.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+struct RunTaintAnalysis {
+    classifications: HashMap<String, Classification>,
+}
+
+impl rustc_driver::Callbacks for RunTaintAnalysis {
+    fn after_analysis<'tcx>(
+        &mut self,
+        compiler: &Compiler,
+        queries: &'tcx Queries<'tcx>,
+    ) -> rustc_driver::Compilation {
+        queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
+            let info = AttrInfo::new(self.classifications.clone());
+            for def_id in tcx.hir().body_owners() {
+                let body = tcx.optimized_mir(def_id.to_def_id());
+                TaintAnalysis::run(tcx, &compiler.session(), &info, body);
+            }
+        });
+        rustc_driver::Compilation::Stop
+    }
+}
+
+/// Compile `src` with `classifications` standing in for what real
+/// `#[taint::*]` attributes on `source`/`sink`/`sanitizer` would have
+/// produced, run `TaintAnalysis` over every function body, and return the
+/// rendered diagnostic output (empty if no sink was ever flagged).
+fn run(src: &str, classifications: HashMap<String, Classification>) -> String {
+    let buffer = SharedBuffer::default();
+    let config = rustc_interface::Config {
+        opts: Options::default(),
+        crate_cfg: Default::default(),
+        crate_check_cfg: Default::default(),
+        input: Input::Str {
+            name: rustc_span::FileName::Custom("mir_regression".into()),
+            input: src.to_string(),
+        },
+        input_path: None,
+        output_dir: None,
+        output_file: None,
+        file_loader: None,
+        diagnostic_output: rustc_session::DiagnosticOutput::Raw(Box::new(buffer.clone())),
+        lint_caps: Default::default(),
+        parse_sess_created: None,
+        register_lints: None,
+        override_queries: None,
+        make_codegen_backend: None,
+        registry: rustc_driver::diagnostics_registry(),
+    };
+
+    rustc_interface::run_compiler(config, |compiler| {
+        let mut callbacks = RunTaintAnalysis { classifications };
+        let _ = callbacks.after_analysis(
+            compiler,
+            compiler.enter(|queries| queries).expect("parsing failed"),
+        );
+    });
+
+    String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap()
+}
+
+fn rejects(labels: &[&str]) -> Classification {
+    Classification::Sink {
+        rejects: labels.iter().map(|l| TaintLabel(l.to_string())).collect(),
+    }
+}
+
+fn source(label: &str) -> Classification {
+    Classification::Source {
+        label: TaintLabel(label.to_string()),
+    }
+}
+
+fn network_source_sink() -> HashMap<String, Classification> {
+    let mut classifications = HashMap::new();
+    classifications.insert("source".to_string(), source("network"));
+    classifications.insert("sink".to_string(), rejects(&["network"]));
+    classifications
+}
+
+/// `TaintedSink`'s stable `code` (see `errors.rs`), used instead of
+/// guessing at rendered diagnostic text to count how many sink calls
+/// were flagged.
+const SINK_ERROR_CODE: &str = "T0001";
+
+#[test]
+fn aggregate_is_field_precise_for_arrays() {
+    // Only `arr[0]` was built from the tainted operand; `arr[1]` was
+    // built from a clean one. Regression for chunk0-4's array-element
+    // precision (the ConstantIndex fix from the chunk0-4 review pass).
+    let diagnostics = run(
+        r#"
+            fn source() -> i32 { 0 }
+            fn sink(_: i32) {}
+            fn main() {
+                let tainted = source();
+                let clean = 0;
+                let arr = [tainted, clean];
+                sink(arr[0]);
+                sink(arr[1]);
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert_eq!(diagnostics.matches(SINK_ERROR_CODE).count(), 1);
+}
+
+#[test]
+fn struct_field_precision() {
+    // Only field `a` was built from the tainted operand, so only the
+    // first `sink` call - reading `p.a` - should be flagged. Regression
+    // for chunk0-3's move-path field sensitivity.
+    let diagnostics = run(
+        r#"
+            struct Pair { a: i32, b: i32 }
+            fn source() -> i32 { 0 }
+            fn sink(_: i32) {}
+            fn main() {
+                let tainted = source();
+                let clean = 0;
+                let p = Pair { a: tainted, b: clean };
+                sink(p.a);
+                sink(p.b);
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert_eq!(diagnostics.matches(SINK_ERROR_CODE).count(), 1);
+}
+
+#[test]
+fn sanitizer_clears_only_its_own_label() {
+    // `combined` carries both the "network" and "env" labels; the
+    // sanitizer only clears "network", so the label set surviving to
+    // `sink` - which rejects both - should still trip on "env" alone.
+    // Regression for chunk0-7's per-label sanitizer/sink sets.
+    let mut classifications = HashMap::new();
+    classifications.insert("net_source".to_string(), source("network"));
+    classifications.insert("env_source".to_string(), source("env"));
+    classifications.insert(
+        "sanitize".to_string(),
+        Classification::Sanitizer {
+            clears: std::iter::once(TaintLabel("network".to_string())).collect(),
+        },
+    );
+    classifications.insert("sink".to_string(), rejects(&["network", "env"]));
+
+    let diagnostics = run(
+        r#"
+            fn net_source() -> i32 { 0 }
+            fn env_source() -> i32 { 0 }
+            fn sanitize(x: i32) -> i32 { x }
+            fn sink(_: i32) {}
+            fn main() {
+                let net = net_source();
+                let env = env_source();
+                let combined = net + env;
+                let cleaned = sanitize(combined);
+                sink(cleaned);
+            }
+        "#,
+        classifications,
+    );
+    assert_eq!(diagnostics.matches(SINK_ERROR_CODE).count(), 1);
+    assert!(diagnostics.contains("env"));
+    assert!(!diagnostics.contains("network"));
+}
+
+#[test]
+fn cast_propagates_taint() {
+    let diagnostics = run(
+        r#"
+            fn source() -> i32 { 0 }
+            fn sink(_: i64) {}
+            fn main() {
+                let tainted = source();
+                sink(tainted as i64);
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert!(!diagnostics.is_empty());
+}
+
+#[test]
+fn len_inherits_taint_from_its_place() {
+    let diagnostics = run(
+        r#"
+            fn source() -> i32 { 0 }
+            fn sink(_: usize) {}
+            fn main() {
+                let tainted = source();
+                let arr = [tainted; 3];
+                sink(arr.len());
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert!(!diagnostics.is_empty());
+}
+
+#[test]
+fn repeat_taints_every_element() {
+    let diagnostics = run(
+        r#"
+            fn source() -> i32 { 0 }
+            fn sink(_: i32) {}
+            fn main() {
+                let tainted = source();
+                let arr = [tainted; 3];
+                sink(arr[2]);
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert!(!diagnostics.is_empty());
+}
+
+#[test]
+fn indirect_assign_propagates_cast_taint() {
+    // `*p = tainted as i64` is an indirect assignment through a
+    // dereferenced place; before, `t_visit_indirect_assign` only
+    // understood a handful of `Rvalue` kinds and silently treated
+    // anything else (including `Cast`) as carrying no taint at all.
+    // Regression for chunk0-2's indirect-assignment coverage gap.
+    let diagnostics = run(
+        r#"
+            fn source() -> i32 { 0 }
+            fn sink(_: i64) {}
+            fn main() {
+                let tainted = source();
+                let mut y: i64 = 0;
+                let p = &mut y;
+                *p = tainted as i64;
+                sink(y);
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert_eq!(diagnostics.matches(SINK_ERROR_CODE).count(), 1);
+}
+
+#[test]
+fn discriminant_propagates_taint() {
+    let diagnostics = run(
+        r#"
+            enum E { A, B }
+            fn source() -> E { E::A }
+            fn sink(_: usize) {}
+            fn main() {
+                let tainted = source();
+                let d = match tainted {
+                    E::A => 0usize,
+                    E::B => 1usize,
+                };
+                sink(d);
+            }
+        "#,
+        network_source_sink(),
+    );
+    assert!(!diagnostics.is_empty());
+}